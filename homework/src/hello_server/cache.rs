@@ -1,28 +1,145 @@
 //! Thread-safe key/value cache.
 
-use std::collections::hash_map::{Entry, HashMap};
+use std::collections::HashMap;
 use std::hash::Hash;
-use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
 
-type MutexHashMap<K> = Mutex<HashMap<K, Arc<(Mutex<bool>, Condvar)>>>;
+/// One cached value plus its links in the usage-order list. `prev` points toward the
+/// most-recently-used side, `next` toward the least-recently-used side.
+#[derive(Debug)]
+struct Entry<K, V> {
+    value: Arc<V>,
+    prev: Option<K>,
+    next: Option<K>,
+}
+
+/// The cache's entries plus an intrusive doubly linked list (keyed by `K` instead of pointers)
+/// tracking usage order, so the least-recently-used entry can be found and evicted in O(1).
+#[derive(Debug)]
+struct Inner<K, V> {
+    entries: HashMap<K, Entry<K, V>>,
+    most_recent: Option<K>,
+    least_recent: Option<K>,
+    capacity: Option<usize>,
+}
+
+impl<K: Eq + Hash + Clone, V> Inner<K, V> {
+    fn new(capacity: Option<usize>) -> Self {
+        Inner {
+            entries: HashMap::new(),
+            most_recent: None,
+            least_recent: None,
+            capacity,
+        }
+    }
+
+    /// Unlink `key` from the usage-order list. Does not touch `entries` itself.
+    fn detach(&mut self, key: &K) {
+        let (prev, next) = {
+            let entry = self.entries.get(key).unwrap();
+            (entry.prev.clone(), entry.next.clone())
+        };
+
+        match &prev {
+            Some(p) => self.entries.get_mut(p).unwrap().next = next.clone(),
+            None => self.most_recent = next.clone(),
+        }
+        match &next {
+            Some(n) => self.entries.get_mut(n).unwrap().prev = prev,
+            None => self.least_recent = prev,
+        }
+    }
+
+    /// Link `key`, which must already be in `entries` with stale/empty `prev`/`next`, at the
+    /// most-recently-used end of the list.
+    fn attach_front(&mut self, key: K) {
+        let old_front = self.most_recent.replace(key.clone());
+        if let Some(front) = &old_front {
+            self.entries.get_mut(front).unwrap().prev = Some(key.clone());
+        } else {
+            self.least_recent = Some(key.clone());
+        }
+
+        let entry = self.entries.get_mut(&key).unwrap();
+        entry.prev = None;
+        entry.next = old_front;
+    }
+
+    /// Move an already-cached key to the most-recently-used end.
+    fn touch(&mut self, key: &K) {
+        if self.most_recent.as_ref() == Some(key) {
+            return;
+        }
+        self.detach(key);
+        self.attach_front(key.clone());
+    }
+
+    /// Insert a freshly computed value at the most-recently-used end, evicting the
+    /// least-recently-used entry if the cache is now over capacity.
+    fn insert(&mut self, key: K, value: Arc<V>) {
+        self.entries.insert(
+            key.clone(),
+            Entry {
+                value,
+                prev: None,
+                next: None,
+            },
+        );
+        self.attach_front(key);
+
+        if let Some(capacity) = self.capacity {
+            while self.entries.len() > capacity {
+                let victim = self.least_recent.clone().unwrap();
+                self.detach(&victim);
+                self.entries.remove(&victim);
+            }
+        }
+    }
+
+    /// Look up a key, marking it most-recently-used on a hit.
+    fn get(&mut self, key: &K) -> Option<Arc<V>> {
+        let value = self.entries.get(key).map(|entry| entry.value.clone());
+        if value.is_some() {
+            self.touch(key);
+        }
+        value
+    }
+}
+
+type CondPerKey<K> = Mutex<HashMap<K, Arc<(Mutex<bool>, Condvar)>>>;
 
 /// Cache that remembers the result for each key.
 #[derive(Debug)]
 pub struct Cache<K, V> {
-    inner: HashMap<K, Arc<V>>,
-    cond_per_key: MutexHashMap<K>,
+    inner: Mutex<Inner<K, V>>,
+    cond_per_key: CondPerKey<K>,
 }
 
-impl<K, V> Default for Cache<K, V> {
+impl<K: Eq + Hash + Clone, V> Default for Cache<K, V> {
     fn default() -> Self {
         Self {
-            inner: HashMap::new(),
+            inner: Mutex::new(Inner::new(None)),
             cond_per_key: Mutex::new(HashMap::new()),
         }
     }
 }
 
 impl<K: Eq + Hash + Clone, V: Clone> Cache<K, V> {
+    /// Create a cache that holds at most `capacity` entries, evicting the least-recently-used one
+    /// on insert once it is full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity > 0);
+        Self {
+            inner: Mutex::new(Inner::new(Some(capacity))),
+            cond_per_key: Mutex::new(HashMap::new()),
+        }
+    }
+
     /// Retrieve the value or insert a new one created by `f`.
     ///
     /// An invocation to this function should not block another invocation with a different key. For
@@ -33,158 +150,159 @@ impl<K: Eq + Hash + Clone, V: Clone> Cache<K, V> {
     /// On the other hand, since `f` may consume a lot of resource (= money), it's undesirable to
     /// duplicate the work. That is, `f` should be run only once for each key. Specifically, even
     /// for concurrent invocations of `get_or_insert_with(key, f)`, `f` is called only once per key.
+    /// The key being computed is only published into the cache (and so only becomes eligible for
+    /// LRU eviction) once `f` has returned, so a slow computation can never be evicted out from
+    /// under itself.
     ///
     /// Hint: the [`Entry`] API may be useful in implementing this function.
     ///
     /// [`Entry`]: https://doc.rust-lang.org/stable/std/collections/hash_map/struct.HashMap.html#method.entry
-    ///
     pub fn get_or_insert_with<F: FnOnce(K) -> V>(&self, key: K, f: F) -> V {
-        let mut inner_clone = self.inner.clone();
-        let value = inner_clone.entry(key.clone());
-
-        match value {
-            Entry::Occupied(entry) => {
-                // value 있음
-                let val = entry.get().clone();
-                (*val).clone()
+        loop {
+            if let Some(value) = self.inner.lock().unwrap().get(&key) {
+                return (*value).clone();
             }
-            Entry::Vacant(entry) => {
-                // value 없음 => cond_mapper에서 계산중인지 확인하기
-                let mut cond_mapper = self.cond_per_key.lock().unwrap();
-                if cond_mapper.contains_key(&key) {
-                    let cond_elem = cond_mapper.get(&key).unwrap();
-
-                    let mut cond_bool = cond_elem.0.lock().unwrap();
-
-                    while *cond_bool {
-                        cond_bool = cond_elem.1.wait(cond_elem.0.lock().unwrap()).unwrap();
-                    }
-                    let calculated_val = inner_clone.get(&key).unwrap().clone();
-
-                    calculated_val.as_ref().clone()
-                } else {
-                    // condVar 생성
-                    let cond_elem = Arc::new((Mutex::new(true), Condvar::new()));
-                    cond_mapper.insert(key.clone(), cond_elem.clone());
-
-                    // 캐시 업데이트
-                    let res = f(key.clone());
-                    inner_clone.insert(key, Arc::new(res.clone()));
-
-                    // condVar 재업데이트
-                    let cloned_cond_elem = cond_elem.clone();
-                    let mut guarded_bool = cloned_cond_elem.0.lock().unwrap();
-                    *guarded_bool = false;
-                    cloned_cond_elem.1.notify_all();
-
-                    res
+
+            let mut cond_mapper = self.cond_per_key.lock().unwrap();
+
+            // Re-check under `cond_per_key`: a computation may have been published (and its claim
+            // removed) between the lookup above and taking this lock.
+            if let Some(value) = self.inner.lock().unwrap().get(&key) {
+                return (*value).clone();
+            }
+
+            if let Some(cond_elem) = cond_mapper.get(&key).cloned() {
+                drop(cond_mapper);
+
+                let (lock, condvar) = &*cond_elem;
+                let mut in_flight = lock.lock().unwrap();
+                while *in_flight {
+                    in_flight = condvar.wait(in_flight).unwrap();
                 }
+                // The value we waited for may since have been evicted by capacity pressure from
+                // other keys; loop back around instead of assuming it is still cached.
+                continue;
             }
+
+            // We are the first to ask for this key: claim it so concurrent callers wait instead of
+            // recomputing it, then compute it outside any lock.
+            let cond_elem = Arc::new((Mutex::new(true), Condvar::new()));
+            cond_mapper.insert(key.clone(), cond_elem.clone());
+            drop(cond_mapper);
+
+            // Catch a panic in `f` so a bad computation can't leave this key's `cond_per_key`
+            // entry stuck at `true` forever, which would deadlock every other thread waiting on
+            // (or later arriving for) the same key.
+            let result = panic::catch_unwind(AssertUnwindSafe(|| f(key.clone())));
+
+            // On success, publish into `inner` *before* clearing `cond_per_key`/notifying: a
+            // waiter must never be able to wake up, find the key absent from both maps, and start
+            // a second, concurrent computation of `f` for the same key.
+            let value = match result {
+                Ok(value) => {
+                    self.inner
+                        .lock()
+                        .unwrap()
+                        .insert(key.clone(), Arc::new(value.clone()));
+                    value
+                }
+                Err(payload) => {
+                    self.cond_per_key.lock().unwrap().remove(&key);
+                    let (lock, condvar) = &*cond_elem;
+                    *lock.lock().unwrap() = false;
+                    condvar.notify_all();
+                    panic::resume_unwind(payload);
+                }
+            };
+
+            self.cond_per_key.lock().unwrap().remove(&key);
+            let (lock, condvar) = &*cond_elem;
+            *lock.lock().unwrap() = false;
+            condvar.notify_all();
+
+            return value;
         }
     }
+
+    /// Number of entries currently cached.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    /// Returns `true` if the cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all cached entries.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner = Inner::new(inner.capacity);
+    }
 }
 
-/*
-
-        // match value {
-        //     Entry::Occupied(mutex_value) => {
-        //         mutex_value.get().clone()
-        //     }
-        //     Entry::Vacant(mutex_value) => {
-        //         let val = self.cond_per_key.get(&arc_key.clone());
-        //
-        //         match val {
-        //             Some(handle) => {
-        //                 let mut_and_cond = handle.clone();
-        //                 let cloned = mut_and_cond.clone();
-        //                 let mut waiting = cloned.0.lock().unwrap();
-        //
-        //                 while *waiting {
-        //                     waiting = mut_and_cond.clone().1.wait(cloned.0.lock().unwrap()).unwrap();
-        //                 }
-        //                 inner.get(&arc_key.clone()).unwrap().clone()
-        //             }
-        //             None => {
-        //                 let is_cond_inserted = self.cond_per_key.get(&arc_key.clone());
-        //                 match is_cond_inserted {
-        //                     Some(handle) => {
-        //                         let mut_and_cond = handle.clone();
-        //                         let cloned = mut_and_cond.clone();
-        //                         let mut waiting = cloned.0.lock().unwrap();
-        //
-        //                         while *waiting {
-        //                             waiting = cloned.1.wait(cloned.0.lock().unwrap()).unwrap();
-        //                         }
-        //                         inner.get(&arc_key.clone()).unwrap().clone()
-        //                     }
-        //                     None => {
-        //                         let flag = Arc::new((Mutex::new(true), Condvar::new()));
-        //                         let mut mut_and_cond = self.cond_per_key.clone();
-        //                         let res = mut_and_cond.insert(arc_key.clone(), flag).unwrap();
-        //
-        //                         let calculated = f((*arc_key).clone());
-        //                         inner.insert(arc_key, calculated.clone());
-        //
-        //                         let cond_var = &res.clone().1;
-        //                         *(res.clone().0.lock().unwrap()) = false;
-        //                         cond_var.notify_all();
-        //
-        //                         calculated
-        //                     }
-        //                 }
-        //             }
-        //         }
-        //     }
-        // }
-    // pub fn get_or_insert_with<F: FnOnce(K) -> V>(&self, key: K, f: F) -> V {
-    //     let inner = &self.inner;
-    //     let arc_key = Arc::new(key);
-    //     let value = inner.entry(arc_key.clone());
-    //
-    //     match value {
-    //         Entry::Occupied(mutex_value) => {
-    //             mutex_value.get()
-    //         }
-    //         Entry::Vacant(mutex_value) => {
-    //             let val = self.cond_per_key.get(&arc_key.clone());
-    //
-    //             match val {
-    //                 Some(handle) => {
-    //                     let mut_and_cond = handle.clone();
-    //                     let mut waiting = mut_and_cond.clone().0.lock().unwrap();
-    //
-    //                     while *waiting {
-    //                         waiting = mut_and_cond.clone().1.wait(mut_and_cond.clone().0.lock().unwrap()).unwrap();
-    //                     }
-    //                     return inner.get(&arc_key.clone()).unwrap().clone();
-    //                 } None => {
-    //                     let is_cond_inserted = self.cond_per_key.get(&arc_key.clone());
-    //                     match is_cond_inserted {
-    //                         Some(handle) => {
-    //                             let mut_and_cond = handle.clone();
-    //                             let mut waiting = mut_and_cond.clone().0.lock().unwrap();
-    //
-    //                             while *waiting {
-    //                                 waiting = mut_and_cond.clone().1.wait(mut_and_cond.clone().0.lock().unwrap()).unwrap();
-    //                             }
-    //                             inner.get(&arc_key.clone())
-    //                         } None => {
-    //                             let flag = Arc::new((Mutex::new(true), Condvar::new()));
-    //                             let mut_and_cond = self.cond_per_key.clone();
-    //
-    //                             let calculated = f((*arc_key).clone());
-    //                             inner.insert(arc_key, calculated.clone());
-    //
-    //                             let cloned = flag.clone();
-    //                             let cond_var = cloned.clone().1;
-    //                             *cloned.0.lock().unwrap() = false;
-    //                             cond_var.notify_all();
-    //
-    //                             calculated
-    //                         }
-    //                     }
-    //                 }
-    //             }
-    //         }
-    //     };
-*/
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn concurrent_callers_share_a_single_computation() {
+        let cache = Cache::<u32, u32>::default();
+        let calls = AtomicUsize::new(0);
+        let barrier = std::sync::Barrier::new(4);
+
+        let results: Vec<u32> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        barrier.wait();
+                        cache.get_or_insert_with(1, |key| {
+                            calls.fetch_add(1, Ordering::SeqCst);
+                            thread::sleep(Duration::from_millis(20));
+                            key * 10
+                        })
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(results.into_iter().all(|v| v == 10));
+    }
+
+    #[test]
+    fn a_panicking_computation_wakes_waiters_without_deadlock() {
+        let cache = Cache::<u32, u32>::default();
+        let claimed = AtomicBool::new(false);
+
+        let (panicker, waiter) = thread::scope(|scope| {
+            let panicker = scope.spawn(|| {
+                std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    cache.get_or_insert_with(1, |_| {
+                        claimed.store(true, Ordering::SeqCst);
+                        thread::sleep(Duration::from_millis(50));
+                        panic!("computation failed")
+                    })
+                }))
+            });
+
+            while !claimed.load(Ordering::SeqCst) {
+                thread::yield_now();
+            }
+
+            // The panicking computation has claimed the key but not yet returned, so this call
+            // must take the "wait on the in-flight computation" path rather than starting its own.
+            let waiter = scope.spawn(|| cache.get_or_insert_with(1, |key| key * 10));
+
+            (panicker.join().unwrap(), waiter.join().unwrap())
+        });
+
+        assert!(panicker.is_err());
+        assert_eq!(waiter, 10);
+        assert_eq!(cache.get_or_insert_with(1, |_| panic!("should be cached")), 10);
+    }
+}
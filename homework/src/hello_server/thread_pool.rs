@@ -1,21 +1,31 @@
 //! Thread pool that joins all thread when dropped.
 
-use std::sync::{Arc, Condvar, Mutex};
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier, Condvar, Mutex};
 use std::thread;
 
-// NOTE: Crossbeam channels are MPMC, which means that you don't need to wrap the receiver in
-// Arc<Mutex<..>>. Just clone the receiver and give it to each worker thread.
-use crossbeam_channel::{unbounded, Sender};
+// A work-stealing queue per worker plus a shared injector, instead of a single MPMC channel: each
+// worker drains its own queue first (cache-friendly), falling back to the injector and then to its
+// siblings' queues only when it has run dry. `Worker` is renamed on import because this module
+// already has its own public `Worker` trait (see below).
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use crossbeam_deque::{Injector, Steal, Stealer, Worker as Deque};
 
-struct Job(Box<dyn FnOnce() + Send + 'static>);
+/// A unit of work sent to a worker thread, or a control signal telling it to stop.
+enum Job {
+    Run(Box<dyn FnOnce() + Send + 'static>),
+    Shutdown,
+}
 
 #[derive(Debug)]
-struct Worker {
+struct WorkerThread {
     _id: usize,
     thread: Option<thread::JoinHandle<()>>,
 }
 
-impl Drop for Worker {
+impl Drop for WorkerThread {
     /// When dropped, the thread's `JoinHandle` must be `join`ed.  If the worker panics, then this
     /// function should panic too.
     ///
@@ -27,12 +37,94 @@ impl Drop for Worker {
     }
 }
 
+/// The pool's shared work-stealing queues: a global injector that `execute` falls back to, plus
+/// every worker's `Stealer` handle so idle workers can steal from busy siblings.
+#[derive(Debug, Default)]
+struct Queues {
+    injector: Injector<Job>,
+    stealers: Mutex<Vec<Stealer<Job>>>,
+}
+
+impl Queues {
+    /// Find a job using the usual priority: this thread's local queue, then a batch from the
+    /// injector, then single jobs stolen from siblings. The stealers are scanned in the order they
+    /// were registered, starting from the first one on every call.
+    fn find_job(&self, local: &Deque<Job>) -> Option<Job> {
+        if let Some(job) = local.pop() {
+            return Some(job);
+        }
+
+        loop {
+            match self.injector.steal_batch_and_pop(local) {
+                Steal::Success(job) => return Some(job),
+                Steal::Retry => continue,
+                Steal::Empty => break,
+            }
+        }
+
+        let stealers = self.stealers.lock().unwrap();
+        for stealer in stealers.iter() {
+            loop {
+                match stealer.steal() {
+                    Steal::Success(job) => return Some(job),
+                    Steal::Retry => continue,
+                    Steal::Empty => break,
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Whether there is, at this instant, any job sitting in any queue.
+    fn has_work(&self, local: &Deque<Job>) -> bool {
+        !local.is_empty()
+            || !self.injector.is_empty()
+            || self.stealers.lock().unwrap().iter().any(|s| !s.is_empty())
+    }
+}
+
+thread_local! {
+    /// The calling thread's local deque, set for the lifetime of a worker thread's `run_worker`
+    /// loop. `execute`/`broadcast` push here when called from inside a running job, for cache
+    /// locality, and fall back to the shared injector otherwise.
+    static CURRENT_QUEUE: RefCell<Option<Deque<Job>>> = const { RefCell::new(None) };
+}
+
+/// Push a job onto the calling thread's local queue if it is a worker thread, otherwise onto the
+/// shared injector.
+fn push_job(queues: &Queues, job: Job) {
+    let overflow = CURRENT_QUEUE.with(|cell| match cell.borrow().as_ref() {
+        Some(local) => {
+            local.push(job);
+            None
+        }
+        None => Some(job),
+    });
+
+    if let Some(job) = overflow {
+        queues.injector.push(job);
+    }
+}
+
 /// Internal data structure for tracking the current job status. This is shared by worker closures
 /// via `Arc` so that the workers can report to the pool that it started/finished a job.
 #[derive(Debug, Default)]
 struct ThreadPoolInner {
     job_count: Mutex<usize>,
     empty_condvar: Condvar,
+    panic_count: AtomicUsize,
+    worker_count: AtomicUsize,
+    // Sleep protocol: `jobs_in_flight` counts jobs that have been enqueued but not yet finished
+    // (incremented on push, decremented on `finish_job` or `shutdown_dequeued`);
+    // `sleeping_workers` counts workers currently parked. A worker increments `sleeping_workers`
+    // while holding `sleep_lock` and re-checks the queues before waiting, and `wake_one` takes
+    // `sleep_lock` before notifying, so a push racing a worker going to sleep cannot lose its
+    // wakeup.
+    sleep_lock: Mutex<()>,
+    sleep_condvar: Condvar,
+    sleeping_workers: AtomicUsize,
+    jobs_in_flight: AtomicUsize,
 }
 
 impl ThreadPoolInner {
@@ -48,6 +140,8 @@ impl ThreadPoolInner {
         let mut res = self.job_count.lock().unwrap();
         *res -= 1;
         self.empty_condvar.notify_one();
+
+        self.jobs_in_flight.fetch_sub(1, Ordering::SeqCst);
     }
 
     /// Wait until the job count becomes 0.
@@ -60,14 +154,270 @@ impl ThreadPoolInner {
             count = self.empty_condvar.wait(count).unwrap();
         }
     }
+
+    /// Record that a job was just enqueued, waking a sleeping worker.
+    ///
+    /// This wakes on every enqueue, not just on an empty-to-nonempty transition: a burst of `N`
+    /// enqueues (e.g. `broadcast`'s `N` jobs) must be able to wake up to `N` parked workers, not
+    /// just the first one, or later calls in the burst can be left with no live thread to pick
+    /// them up.
+    fn job_enqueued(&self) {
+        self.jobs_in_flight.fetch_add(1, Ordering::SeqCst);
+        self.wake_one();
+    }
+
+    /// Record that a dequeued `Job::Shutdown` was handled. Unlike a `Job::Run`, it never went
+    /// through `start_job`/`finish_job`, so only `jobs_in_flight` needs to be settled here.
+    fn shutdown_dequeued(&self) {
+        self.jobs_in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Wake a single sleeping worker, if any are currently parked.
+    fn wake_one(&self) {
+        if self.sleeping_workers.load(Ordering::SeqCst) > 0 {
+            let _guard = self.sleep_lock.lock().unwrap();
+            self.sleep_condvar.notify_one();
+        }
+    }
+
+    /// Park the calling worker until woken, unless `still_idle` finds work in the critical window
+    /// right after announcing sleep — that final check is what prevents the lost-wakeup race
+    /// against a concurrent `job_enqueued`/`wake_one`.
+    fn sleep_until_woken(&self, still_idle: impl Fn() -> bool) {
+        let guard = self.sleep_lock.lock().unwrap();
+        self.sleeping_workers.fetch_add(1, Ordering::SeqCst);
+
+        if still_idle() {
+            let _guard = self.sleep_condvar.wait(guard).unwrap();
+        }
+
+        self.sleeping_workers.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Guards a worker thread's receive-and-run loop. `active` stays `true` for the whole lifetime of
+/// the loop; the only way `drop` sees it still `true` is that the stack is unwinding because the
+/// job running inside the loop panicked (the normal exit paths flip it to `false` first). In that
+/// case the guard respawns a replacement worker before letting the panic keep unwinding, so the
+/// pool's thread count and `join()` accounting both recover.
+///
+/// NOTE: jobs still sitting in the panicked worker's local queue are not moved by the respawn. Its
+/// `Stealer` stays registered in `Queues::stealers` after the `Deque` is dropped with the thread,
+/// so those jobs are picked up only by stealing.
+struct WorkerSentinel {
+    id: usize,
+    queues: Arc<Queues>,
+    inner: Arc<ThreadPoolInner>,
+    workers: Arc<Mutex<Vec<WorkerThread>>>,
+    thread_name: Option<String>,
+    thread_stack_size: Option<usize>,
+    active: bool,
+}
+
+impl Drop for WorkerSentinel {
+    fn drop(&mut self) {
+        if !self.active {
+            return;
+        }
+
+        self.inner.panic_count.fetch_add(1, Ordering::SeqCst);
+        self.inner.worker_count.fetch_sub(1, Ordering::SeqCst);
+
+        let mut worker = spawn_worker(
+            self.id,
+            self.queues.clone(),
+            self.inner.clone(),
+            self.workers.clone(),
+            self.thread_name.as_deref(),
+            self.thread_stack_size,
+        );
+
+        let mut workers = self.workers.lock().unwrap();
+        match workers.iter_mut().find(|w| w._id == self.id) {
+            Some(stale) => {
+                // This drop runs on the panicking thread itself, which has not finished
+                // unwinding yet, so `thread.join()`-ing its own stale handle here would deadlock.
+                // Detach it instead (same as letting a `WorkerThread` drop without joining) and
+                // swap in the freshly spawned replacement under the same id.
+                stale.thread.take();
+                stale.thread = worker.thread.take();
+            }
+            None => workers.push(worker),
+        }
+
+        // The panicked job never reached its own `finish_job()` call, so the sentinel accounts
+        // for it here to keep `wait_empty` (and thus `join`) making progress.
+        self.inner.finish_job();
+    }
+}
+
+/// Body of a worker thread: pop jobs from the work-stealing queues and run them until a
+/// [`Job::Shutdown`] is received, sleeping whenever every queue runs dry.
+fn run_worker(
+    id: usize,
+    local: Deque<Job>,
+    queues: Arc<Queues>,
+    inner: Arc<ThreadPoolInner>,
+    workers: Arc<Mutex<Vec<WorkerThread>>>,
+    thread_name: Option<String>,
+    thread_stack_size: Option<usize>,
+) {
+    CURRENT_QUEUE.with(|cell| *cell.borrow_mut() = Some(local));
+
+    let mut sentinel = WorkerSentinel {
+        id,
+        queues: queues.clone(),
+        inner: inner.clone(),
+        workers,
+        thread_name,
+        thread_stack_size,
+        active: true,
+    };
+
+    loop {
+        let job = CURRENT_QUEUE.with(|cell| {
+            let cell = cell.borrow();
+            let local = cell.as_ref().unwrap();
+            queues.find_job(local)
+        });
+
+        match job {
+            Some(Job::Run(job)) => {
+                job();
+                sentinel.inner.finish_job();
+            }
+            Some(Job::Shutdown) => {
+                sentinel.active = false;
+                sentinel.inner.worker_count.fetch_sub(1, Ordering::SeqCst);
+                sentinel.inner.shutdown_dequeued();
+                break;
+            }
+            None => {
+                sentinel.inner.sleep_until_woken(|| {
+                    CURRENT_QUEUE.with(|cell| {
+                        let cell = cell.borrow();
+                        let local = cell.as_ref().unwrap();
+                        !queues.has_work(local)
+                    })
+                });
+            }
+        }
+    }
+
+    CURRENT_QUEUE.with(|cell| *cell.borrow_mut() = None);
+}
+
+/// Spawn a worker thread running [`run_worker`], applying the pool's configured thread name and
+/// stack size, and registering its `Stealer` so siblings can steal from it.
+fn spawn_worker(
+    id: usize,
+    queues: Arc<Queues>,
+    inner: Arc<ThreadPoolInner>,
+    workers: Arc<Mutex<Vec<WorkerThread>>>,
+    thread_name: Option<&str>,
+    thread_stack_size: Option<usize>,
+) -> WorkerThread {
+    let local = Deque::new_lifo();
+    queues.stealers.lock().unwrap().push(local.stealer());
+
+    let mut builder = thread::Builder::new();
+    if let Some(name) = thread_name {
+        builder = builder.name(format!("{name}-{id}"));
+    }
+    if let Some(stack_size) = thread_stack_size {
+        builder = builder.stack_size(stack_size);
+    }
+
+    let name = thread_name.map(str::to_owned);
+    inner.worker_count.fetch_add(1, Ordering::SeqCst);
+    let thread = builder
+        .spawn(move || run_worker(id, local, queues, inner, workers, name, thread_stack_size))
+        .expect("failed to spawn worker thread");
+
+    WorkerThread {
+        _id: id,
+        thread: Some(thread),
+    }
+}
+
+/// Builder for [`ThreadPool`], configuring the number of threads and how they are spawned.
+#[derive(Debug, Clone, Default)]
+pub struct Builder {
+    num_threads: Option<usize>,
+    thread_name: Option<String>,
+    thread_stack_size: Option<usize>,
+}
+
+impl Builder {
+    /// Create a new, unconfigured `Builder`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of worker threads. Defaults to 1.
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    /// Set the name worker threads are spawned with (suffixed with `-<id>`).
+    pub fn thread_name(mut self, name: impl Into<String>) -> Self {
+        self.thread_name = Some(name.into());
+        self
+    }
+
+    /// Set the stack size worker threads are spawned with.
+    pub fn thread_stack_size(mut self, size: usize) -> Self {
+        self.thread_stack_size = Some(size);
+        self
+    }
+
+    /// Build the configured [`ThreadPool`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_threads` is 0.
+    pub fn build(self) -> ThreadPool {
+        let size = self.num_threads.unwrap_or(1);
+        assert!(size > 0);
+
+        let pool_inner = Arc::new(ThreadPoolInner::default());
+        let queues = Arc::new(Queues::default());
+        let workers_handle = Arc::new(Mutex::new(Vec::new()));
+
+        let mut workers = Vec::with_capacity(size);
+        for idx in 0..size {
+            workers.push(spawn_worker(
+                idx,
+                queues.clone(),
+                pool_inner.clone(),
+                workers_handle.clone(),
+                self.thread_name.as_deref(),
+                self.thread_stack_size,
+            ));
+        }
+        *workers_handle.lock().unwrap() = workers;
+
+        ThreadPool {
+            workers: workers_handle,
+            queues,
+            pool_inner,
+            next_id: Arc::new(AtomicUsize::new(size)),
+            thread_name: self.thread_name,
+            thread_stack_size: self.thread_stack_size,
+        }
+    }
 }
 
 /// Thread pool.
 #[derive(Debug)]
 pub struct ThreadPool {
-    _workers: Vec<Worker>,
-    job_sender: Option<Sender<Job>>,
+    workers: Arc<Mutex<Vec<WorkerThread>>>,
+    queues: Arc<Queues>,
     pool_inner: Arc<ThreadPoolInner>,
+    next_id: Arc<AtomicUsize>,
+    thread_name: Option<String>,
+    thread_stack_size: Option<usize>,
 }
 
 impl ThreadPool {
@@ -77,47 +427,88 @@ impl ThreadPool {
     ///
     /// Panics if `size` is 0.
     pub fn new(size: usize) -> Self {
-        assert!(size > 0);
-        let mut workers = Vec::with_capacity(size);
+        Builder::new().num_threads(size).build()
+    }
 
-        let (sender, receiver) = unbounded::<Job>();
-        let arc_receiver = Arc::new(receiver);
-        // let pair = Arc::new((Mutex::new(0usize), Condvar::new()));
-        // let cloned: Arc<(Mutex<usize>, Condvar)> = pair.clone();
-        let pool_inner = Arc::new(ThreadPoolInner {
-            job_count: Mutex::new(0usize),
-            empty_condvar: Condvar::new(),
-        });
+    /// Resize the pool to `n` worker threads, growing or shrinking it as needed.
+    ///
+    /// Growing spawns additional workers sharing the existing queues. Shrinking enqueues one
+    /// [`Job::Shutdown`] per thread to remove, which makes exactly that many workers break out of
+    /// their loop; the rest keep serving jobs undisturbed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is 0.
+    pub fn set_num_threads(&self, n: usize) {
+        assert!(n > 0);
 
-        for idx in 0..size {
-            let receiver = arc_receiver.clone();
-            let inner = pool_inner.clone();
-            let thread = thread::spawn(move || loop {
-                let message = receiver.recv();
-
-                match message {
-                    Ok(job) => {
-                        inner.start_job();
-                        job.0();
-                        inner.finish_job();
+        let mut workers = self.workers.lock().unwrap();
+        let current = workers.len();
+
+        match n.cmp(&current) {
+            std::cmp::Ordering::Greater => {
+                for _ in current..n {
+                    let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+                    workers.push(spawn_worker(
+                        id,
+                        self.queues.clone(),
+                        self.pool_inner.clone(),
+                        self.workers.clone(),
+                        self.thread_name.as_deref(),
+                        self.thread_stack_size,
+                    ));
+                }
+            }
+            std::cmp::Ordering::Less => {
+                let to_remove = current - n;
+                drop(workers);
+                for _ in 0..to_remove {
+                    self.enqueue(Job::Shutdown);
+                }
+
+                // Reap the threads that accepted a `Job::Shutdown`, leaving the rest untouched.
+                let mut reaped = 0;
+                while reaped < to_remove {
+                    let mut workers = self.workers.lock().unwrap();
+                    let mut idx = 0;
+                    while idx < workers.len() && reaped < to_remove {
+                        if workers[idx]
+                            .thread
+                            .as_ref()
+                            .is_some_and(|t| t.is_finished())
+                        {
+                            let mut worker = workers.remove(idx);
+                            if let Some(t) = worker.thread.take() {
+                                t.join().unwrap();
+                            }
+                            reaped += 1;
+                        } else {
+                            idx += 1;
+                        }
                     }
-                    Err(_) => {
-                        break;
+                    drop(workers);
+                    if reaped < to_remove {
+                        thread::yield_now();
                     }
                 }
-            });
-
-            workers.push(Worker {
-                _id: idx,
-                thread: Some(thread),
-            })
+            }
+            std::cmp::Ordering::Equal => {}
         }
+    }
 
-        ThreadPool {
-            _workers: workers,
-            job_sender: Some(sender),
-            pool_inner,
+    /// Push a job onto the work-stealing queues and wake a sleeping worker if needed.
+    ///
+    /// `job_count` is incremented here, at enqueue time, rather than when a worker dequeues the
+    /// job: `join`/`wait_empty` must see a job as outstanding the moment it is queued, or a call
+    /// racing a job that hasn't been picked up by any worker yet could return before that job
+    /// ever ran. `Job::Shutdown` is a control signal, not a job, so it never goes through
+    /// `start_job`/`finish_job`.
+    fn enqueue(&self, job: Job) {
+        if matches!(job, Job::Run(_)) {
+            self.pool_inner.start_job();
         }
+        push_job(&self.queues, job);
+        self.pool_inner.job_enqueued();
     }
 
     /// Execute a new job in the thread pool.
@@ -125,10 +516,7 @@ impl ThreadPool {
     where
         F: FnOnce() + Send + 'static,
     {
-        let sender = self.job_sender.as_ref().unwrap();
-        let job = Job(Box::new(f));
-
-        sender.send(job).unwrap()
+        self.enqueue(Job::Run(Box::new(f)));
     }
 
     /// Block the current thread until all jobs in the pool have been executed.
@@ -137,15 +525,118 @@ impl ThreadPool {
     pub fn join(&self) {
         self.pool_inner.wait_empty();
     }
+
+    /// Number of jobs that have panicked so far.
+    ///
+    /// A panicked job does not take its worker down with it: the pool respawns a replacement
+    /// thread automatically, and the panic is only reflected here.
+    pub fn panic_count(&self) -> usize {
+        self.pool_inner.panic_count.load(Ordering::SeqCst)
+    }
+
+    /// Number of worker threads currently running.
+    ///
+    /// This tracks `set_num_threads` resizing as well as panic-triggered respawns, so it always
+    /// reflects the live thread count rather than the size the pool was originally built with.
+    pub fn num_threads(&self) -> usize {
+        self.pool_inner.worker_count.load(Ordering::SeqCst)
+    }
+
+    /// Submit a job and get back a handle for its eventual result.
+    ///
+    /// Unlike [`execute`](ThreadPool::execute), a panic inside `f` is caught and delivered through
+    /// the returned [`JobHandle`] instead of unwinding the worker thread.
+    pub fn submit<F, T>(&self, f: F) -> JobHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let slot = Arc::new((Mutex::new(None), Condvar::new()));
+        let handle_slot = slot.clone();
+
+        self.execute(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(f));
+            let (lock, condvar) = &*handle_slot;
+            *lock.lock().unwrap() = Some(result);
+            condvar.notify_one();
+        });
+
+        JobHandle { slot }
+    }
+
+    /// Run `f` exactly once on each worker thread, passing it a 0-based index, and block until
+    /// every invocation has finished.
+    ///
+    /// Internally this enqueues one broadcast job per worker; each job waits on a shared
+    /// [`Barrier`] before calling `f`, so a worker that finishes early cannot move on to a second
+    /// broadcast job while a sibling is still busy with unrelated work — by the time any job is
+    /// allowed past the barrier, all of them have already been dequeued by distinct worker
+    /// threads. This makes `broadcast` suitable for per-thread initialization, cache warming, or
+    /// collecting per-thread stats, none of which `execute` can express, since it does not choose
+    /// which thread runs a job.
+    pub fn broadcast<F>(&self, f: F)
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        let size = self.workers.lock().unwrap().len();
+        let f = Arc::new(f);
+        let barrier = Arc::new(Barrier::new(size));
+        let latch = Arc::new((Mutex::new(size), Condvar::new()));
+
+        for idx in 0..size {
+            let f = f.clone();
+            let barrier = barrier.clone();
+            let latch = latch.clone();
+
+            self.execute(move || {
+                barrier.wait();
+                f(idx);
+
+                let (lock, condvar) = &*latch;
+                let mut remaining = lock.lock().unwrap();
+                *remaining -= 1;
+                if *remaining == 0 {
+                    condvar.notify_all();
+                }
+            });
+        }
+
+        let (lock, condvar) = &*latch;
+        let mut remaining = lock.lock().unwrap();
+        while *remaining != 0 {
+            remaining = condvar.wait(remaining).unwrap();
+        }
+    }
+}
+
+/// A handle to a job's eventual result, returned by [`ThreadPool::submit`].
+pub struct JobHandle<T> {
+    slot: Arc<(Mutex<Option<thread::Result<T>>>, Condvar)>,
+}
+
+impl<T> JobHandle<T> {
+    /// Block until the job completes, returning its value or the panic payload it unwound with.
+    pub fn join(self) -> thread::Result<T> {
+        let (lock, condvar) = &*self.slot;
+        let mut slot = lock.lock().unwrap();
+        while slot.is_none() {
+            slot = condvar.wait(slot).unwrap();
+        }
+        slot.take().unwrap()
+    }
 }
 
 impl Drop for ThreadPool {
     /// When dropped, all worker threads' `JoinHandle` must be `join`ed. If the thread panicked,
     /// then this function should panic too.
     fn drop(&mut self) {
-        drop(self.job_sender.take());
+        let size = self.workers.lock().unwrap().len();
+        for _ in 0..size {
+            self.enqueue(Job::Shutdown);
+        }
 
-        for worker in &mut self._workers {
+        let mut workers = self.workers.lock().unwrap();
+        for worker in workers.iter_mut() {
             if let Some(thread) = worker.thread.take() {
                 thread.join().unwrap();
             }
@@ -153,3 +644,139 @@ impl Drop for ThreadPool {
         }
     }
 }
+
+/// A reusable per-thread handler for [`TypedPool`].
+///
+/// Unlike the boxed `FnOnce` jobs run by [`ThreadPool`], a `Worker` is constructed once per
+/// thread, so it can hold state that should persist across inputs (a database connection, a
+/// parser, a warmed-up cache).
+pub trait Worker<In, Out> {
+    /// Process a single input and produce its output.
+    fn execute(&self, input: In) -> Out;
+}
+
+/// Thread pool whose threads each run a user-supplied [`Worker`] and stream typed results back to
+/// the caller over a result channel, instead of boxed `FnOnce()` closures.
+#[derive(Debug)]
+pub struct TypedPool<In, Out> {
+    workers: Vec<WorkerThread>,
+    job_sender: Option<Sender<In>>,
+    result_receiver: Receiver<Out>,
+    pool_inner: Arc<ThreadPoolInner>,
+}
+
+impl<In, Out> TypedPool<In, Out>
+where
+    In: Send + 'static,
+    Out: Send + 'static,
+{
+    /// Create a new `TypedPool` with `size` threads, each running its own handler built by
+    /// `make_worker(idx)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0.
+    pub fn new<W, F>(size: usize, make_worker: F) -> Self
+    where
+        W: Worker<In, Out> + Send + 'static,
+        F: Fn(usize) -> W,
+    {
+        assert!(size > 0);
+        let mut workers = Vec::with_capacity(size);
+
+        let (job_sender, job_receiver) = unbounded::<In>();
+        let (result_sender, result_receiver) = unbounded::<Out>();
+        let job_receiver = Arc::new(job_receiver);
+        let pool_inner = Arc::new(ThreadPoolInner::default());
+
+        for idx in 0..size {
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            let inner = pool_inner.clone();
+            let worker = make_worker(idx);
+
+            let thread = thread::spawn(move || {
+                while let Ok(input) = job_receiver.recv() {
+                    let output = worker.execute(input);
+                    inner.finish_job();
+                    // The receiver may already be gone; a stale result is simply dropped.
+                    let _ = result_sender.send(output);
+                }
+            });
+
+            workers.push(WorkerThread {
+                _id: idx,
+                thread: Some(thread),
+            });
+        }
+
+        TypedPool {
+            workers,
+            job_sender: Some(job_sender),
+            result_receiver,
+            pool_inner,
+        }
+    }
+
+    /// Submit a new input to the pool.
+    pub fn submit(&self, input: In) {
+        self.pool_inner.start_job();
+        self.job_sender.as_ref().unwrap().send(input).unwrap();
+    }
+
+    /// Block the current thread until all submitted inputs have been processed.
+    pub fn join(&self) {
+        self.pool_inner.wait_empty();
+    }
+
+    /// Iterator over results as they arrive, blocking as needed. Callers that submitted `n` inputs
+    /// typically drain exactly `n` of them with `results().take(n)`.
+    pub fn results(&self) -> impl Iterator<Item = Out> + '_ {
+        self.result_receiver.iter()
+    }
+}
+
+impl<In, Out> Drop for TypedPool<In, Out> {
+    /// When dropped, all worker threads' `JoinHandle` must be `join`ed. If a thread panicked, then
+    /// this function should panic too.
+    fn drop(&mut self) {
+        drop(self.job_sender.take());
+
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                thread.join().unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_and_drop_survive_a_panicked_job() {
+        let pool = ThreadPool::new(2);
+        pool.execute(|| panic!("boom"));
+        pool.join();
+        assert_eq!(pool.panic_count(), 1);
+        // Would previously panic inside `drop` with `Result::unwrap()` on an `Err`, because the
+        // panicked worker's stale, already-finished `JoinHandle` was never removed from `workers`.
+        drop(pool);
+    }
+
+    #[test]
+    fn broadcast_still_reaches_every_worker_after_a_panic() {
+        let pool = ThreadPool::new(3);
+        pool.execute(|| panic!("boom"));
+        pool.join();
+        assert_eq!(pool.num_threads(), 3);
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let hits_clone = hits.clone();
+        pool.broadcast(move |_| {
+            hits_clone.fetch_add(1, Ordering::SeqCst);
+        });
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+}